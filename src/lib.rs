@@ -1,110 +1,129 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::ops::{Deref, DerefMut};
 
 use bitvec::prelude::*;
+use rand::prelude::*;
+
+// Values above 9 are encoded as letters ('a', 'b', ...), so parsing and
+// printing both go through this radix. `char::from_digit` only accepts
+// values up to `RADIX - 1`, which caps the board at `C <= 5` (25x25):
+// `Puzzle::parse` asserts this for every other entry point, since they
+// all end up constructing a puzzle through it.
+const RADIX: u32 = 36;
+
+/// Geometry of an N×N sudoku built from `C`×`C` boxes: `L = C * C` is the
+/// side length of the board, `A = L * L` the number of cells.
+pub struct Board<const C: usize>;
+
+impl<const C: usize> Board<C> {
+    pub const L: usize = C * C;
+    pub const A: usize = Self::L * Self::L;
+}
 
-const SUDOKU_L: u32 = 9;
-const SUDOKU_C: u32 = 3;
-const SUDOKU_A: usize = 81;
-
-#[derive(Debug, PartialEq)]
-struct Mask {
-    bits: BitArr!(for SUDOKU_A, in u32),
+#[derive(Debug, Clone, PartialEq)]
+struct Mask<const C: usize> {
+    bits: BitVec<u32, Lsb0>,
 }
 
-impl Mask {
+impl<const C: usize> Mask<C> {
+    const L: usize = Board::<C>::L;
+    const A: usize = Board::<C>::A;
+
     fn new() -> Self {
-        Self { bits: bitarr!(u32, Lsb0; 0; 81) }
+        Self { bits: BitVec::repeat(false, Self::A) }
     }
 
+    // `index` is the top-left anchor of the box.
     fn cell(index: u32) -> Self {
-        assert!(index < SUDOKU_A as u32 && index % SUDOKU_L < 7);
-
-        let mut bits = bitarr!(u32, Lsb0;
-            1, 1, 1, 0, 0, 0, 0, 0, 0,
-            1, 1, 1, 0, 0, 0, 0, 0, 0,
-            1, 1, 1, 0, 0, 0, 0, 0, 0,
-            0, 0, 0, 0, 0, 0, 0, 0, 0,
-            0, 0, 0, 0, 0, 0, 0, 0, 0,
-            0, 0, 0, 0, 0, 0, 0, 0, 0,
-            0, 0, 0, 0, 0, 0, 0, 0, 0,
-            0, 0, 0, 0, 0, 0, 0, 0, 0,
-            0, 0, 0, 0, 0, 0, 0, 0, 0,
-        );
+        let mut mask = Self::new();
 
-        bits.shift_right(index as usize);
+        for r in 0..C {
+            for c in 0..C {
+                mask.bits.set(index as usize + Self::L * r + c, true);
+            }
+        }
 
-        Self { bits }
+        mask
     }
 
+    // `index` is the first cell of the row.
     fn row(index: u32) -> Self {
-        assert!(index % SUDOKU_L == 0);
+        let mut mask = Self::new();
 
-        let mut bits = bitarr!(u32, Lsb0;
-            1, 1, 1, 1, 1, 1, 1, 1, 1,
-            0, 0, 0, 0, 0, 0, 0, 0, 0,
-            0, 0, 0, 0, 0, 0, 0, 0, 0,
-            0, 0, 0, 0, 0, 0, 0, 0, 0,
-            0, 0, 0, 0, 0, 0, 0, 0, 0,
-            0, 0, 0, 0, 0, 0, 0, 0, 0,
-            0, 0, 0, 0, 0, 0, 0, 0, 0,
-            0, 0, 0, 0, 0, 0, 0, 0, 0,
-            0, 0, 0, 0, 0, 0, 0, 0, 0,
-        );
-
-        bits.shift_right(index as usize);
+        for c in 0..Self::L {
+            mask.bits.set(index as usize + c, true);
+        }
 
-        Self { bits }
+        mask
     }
 
+    // `index` is the first cell of the column.
     fn column(index: u32) -> Self {
-        assert!(index < SUDOKU_L);
+        let mut mask = Self::new();
 
-        let mut bits = bitarr!(u32, Lsb0;
-            1, 0, 0, 0, 0, 0, 0, 0, 0,
-            1, 0, 0, 0, 0, 0, 0, 0, 0,
-            1, 0, 0, 0, 0, 0, 0, 0, 0,
-            1, 0, 0, 0, 0, 0, 0, 0, 0,
-            1, 0, 0, 0, 0, 0, 0, 0, 0,
-            1, 0, 0, 0, 0, 0, 0, 0, 0,
-            1, 0, 0, 0, 0, 0, 0, 0, 0,
-            1, 0, 0, 0, 0, 0, 0, 0, 0,
-            1, 0, 0, 0, 0, 0, 0, 0, 0,
-        );
-
-        bits.shift_right(index as usize);
+        for r in 0..Self::L {
+            mask.bits.set(index as usize + Self::L * r, true);
+        }
 
-        Self { bits }
+        mask
     }
 }
 
-impl Deref for Mask {
+impl<const C: usize> Deref for Mask<C> {
     type Target = BitSlice<u32, Lsb0>;
 
     fn deref(&self) -> &Self::Target {
-        &self.bits[..SUDOKU_A]
+        &self.bits[..]
     }
 }
 
-impl DerefMut for Mask {
+impl<const C: usize> DerefMut for Mask<C> {
     fn deref_mut(&mut self) -> &mut Self::Target {
-        &mut self.bits[..SUDOKU_A]
+        &mut self.bits[..]
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 struct CorruptLayerError {
     value: u32,
 }
 
-#[derive(Debug)]
-struct Layer {
+#[derive(Debug, PartialEq)]
+pub enum SudokuError {
+    InvalidLength { expected: usize, found: usize },
+    InvalidDigit { character: char },
+    CorruptLayer { value: u32 },
+    Unsolvable,
+}
+
+impl From<CorruptLayerError> for SudokuError {
+    fn from(error: CorruptLayerError) -> Self {
+        SudokuError::CorruptLayer { value: error.value }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Technique {
+    HiddenSingle,
+    NakedSingle,
+    LockedCandidate,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SolveStep {
+    pub technique: Technique,
+    pub value: u32,
+    pub index: u32,
+}
+
+#[derive(Debug, Clone)]
+struct Layer<const C: usize> {
     indices: Vec<u32>,
-    mask: Mask,
+    mask: Mask<C>,
     value: u32,
 }
 
-impl Layer {
+impl<const C: usize> Layer<C> {
     fn new(value: u32) -> Self {
         Self {
             indices: vec![],
@@ -118,53 +137,77 @@ impl Layer {
     }
 
     fn occupy(&mut self, index: u32) {
-        let mask = Mask::cell(SUDOKU_C*SUDOKU_L * (index / (SUDOKU_C*SUDOKU_L)) + SUDOKU_C*((index % SUDOKU_L) / SUDOKU_C));
+        let l = Board::<C>::L as u32;
+        let c = C as u32;
+
+        let mask = Mask::<C>::cell(c*l * (index / (c*l)) + c*((index % l) / c));
         *self.mask |= mask.bits;
 
-        let mask = Mask::row(SUDOKU_L*(index / SUDOKU_L));
+        let mask = Mask::<C>::row(l*(index / l));
         *self.mask |= mask.bits;
 
-        let mask = Mask::column(index % SUDOKU_L);
+        let mask = Mask::<C>::column(index % l);
         *self.mask |= mask.bits;
 
         self.indices.push(index);
     }
 
     fn is_solved(&self) -> bool {
-        self.indices.len() == SUDOKU_L as usize
+        self.indices.len() == Board::<C>::L
     }
 }
 
-pub struct Puzzle {
-    layers: [Layer; SUDOKU_L as usize],
+#[derive(Clone)]
+pub struct Puzzle<const C: usize> {
+    layers: Vec<Layer<C>>,
 }
 
-impl Puzzle {
-    pub fn parse(feed: &str) -> Self {
-        let mut layers: Vec<Layer> = (1..=SUDOKU_L)
-            .map(|i| Layer::new(i))
+impl<const C: usize> Puzzle<C> {
+    pub fn parse(feed: &str) -> Result<Self, SudokuError> {
+        // RADIX = 36 can't encode a board any bigger than this as single
+        // characters; every other constructor funnels through `parse`, so
+        // checking it once here is enough.
+        assert!(Board::<C>::L < RADIX as usize);
+
+        let l = Board::<C>::L as u32;
+        let a = Board::<C>::A;
+
+        let found = feed.chars().count();
+        if found != a {
+            return Err(SudokuError::InvalidLength { expected: a, found });
+        }
+
+        let mut layers: Vec<Layer<C>> = (1..=l)
+            .map(Layer::new)
             .collect();
 
-        feed
-            .chars()
-            .map(|c| c.to_digit(10).unwrap())
-            .enumerate()
-            .filter(|&(_, value)| value != 0)
-            .for_each(|(index, value)| {
-                layers
-                    .iter_mut()
-                    .for_each(|layer| {
-                        if value == layer.value {
-                            layer.occupy(index as u32);
-                        } else {
-                            layer.blot(index as u32);
-                        };
-                    });
-            });
+        for (index, character) in feed.chars().enumerate() {
+            let value = character.to_digit(RADIX)
+                .filter(|&value| value <= l)
+                .ok_or(SudokuError::InvalidDigit { character })?;
+
+            if value == 0 {
+                continue;
+            }
+
+            layers
+                .iter_mut()
+                .for_each(|layer| {
+                    if value == layer.value {
+                        layer.occupy(index as u32);
+                    } else {
+                        layer.blot(index as u32);
+                    };
+                });
+        }
 
-        Self {
-            layers: layers.try_into().unwrap(),
+        let puzzle = Self { layers };
+
+        for segment in Self::build_segments() {
+            segment.count_open(&puzzle)?;
         }
+
+        Ok(puzzle)
     }
 
     fn update(&mut self, value: u32, index: u32) {
@@ -183,31 +226,399 @@ impl Puzzle {
         self.layers.iter().all(|layer| layer.is_solved())
     }
 
-    pub fn solve(&mut self) {
-        let mut segments: Vec<Box<dyn Segment>> = vec![];
+    fn build_segments() -> Vec<Box<dyn Segment<C>>> {
+        let l = Board::<C>::L as u32;
+        let c = C as u32;
+        let mut segments: Vec<Box<dyn Segment<C>>> = vec![];
 
-        for index in 1..=9 {
+        for index in 1..=l {
             segments.push(Box::new(Row::new(index)));
             segments.push(Box::new(Column::new(index)));
 
-            let i = (index - 1) / SUDOKU_C + 1;
-            let j = (index - 1) % SUDOKU_C + 1;
+            let i = (index - 1) / c + 1;
+            let j = (index - 1) % c + 1;
             segments.push(Box::new(Cell::new(i, j)));
         }
 
-        while !self.is_solved() {
-            segments.sort_by_key(|segment| SUDOKU_L as usize - segment.count_open(&self));
+        segments
+    }
+
+    fn occupied_count(&self) -> usize {
+        self.layers.iter().map(|layer| layer.indices.len()).sum()
+    }
+
+    fn is_occupied(&self, index: u32) -> bool {
+        self.layers.iter().any(|layer| layer.indices.contains(&index))
+    }
+
+    fn candidates(&self, index: u32) -> Vec<u32> {
+        if self.is_occupied(index) {
+            return vec![];
+        }
+
+        (1..=Board::<C>::L as u32)
+            .filter(|&value| !self.layers[(value - 1) as usize].mask[index as usize])
+            .collect()
+    }
 
-            segments
-                .iter()
-                .for_each(|segment| segment.iterate(self));
+    // Runs the deduction passes (hidden singles) to a fixpoint. Returns
+    // `CorruptLayerError` if a segment ends up with more than one position
+    // for a value, which means this branch of the board is contradictory.
+    // `solve`/`search` only ever escalate from this to brute-force search,
+    // not to the naked-single/locked-candidate techniques below: those are
+    // deliberately kept behind `solve_logical`, which is meant to produce a
+    // technique-rated trace rather than to make the solver itself faster.
+    fn deduce(&mut self) -> Result<(), CorruptLayerError> {
+        let mut segments = Self::build_segments();
+        let l = Board::<C>::L;
+
+        loop {
+            let before = self.occupied_count();
+
+            let mut ordered = Vec::with_capacity(segments.len());
+            for segment in segments.into_iter() {
+                let open = segment.count_open(self)?;
+                ordered.push((open, segment));
+            }
+            ordered.sort_by_key(|(open, _)| l - open);
+
+            for (_, segment) in &ordered {
+                segment.iterate(self)?;
+            }
+
+            let mut kept = Vec::with_capacity(ordered.len());
+            for (_, segment) in ordered {
+                if segment.count_open(self)? > 0 {
+                    kept.push(segment);
+                }
+            }
+            segments = kept;
 
-            segments.retain(|segment| segment.count_open(&self) > 0);
+            if self.occupied_count() == before {
+                break;
+            }
         }
+
+        Ok(())
+    }
+
+    // The empty cell with the fewest candidates (MRV heuristic), along with
+    // those candidates. `None` means every cell is occupied.
+    fn branch_cell(&self) -> Option<(u32, Vec<u32>)> {
+        (0..Board::<C>::A as u32)
+            .filter(|&index| !self.is_occupied(index))
+            .map(|index| (index, self.candidates(index)))
+            .min_by_key(|(_, candidates)| candidates.len())
+    }
+
+    // Minimum-remaining-value backtracking search, used once deduction
+    // alone stalls before the puzzle is solved.
+    fn search(&mut self) -> bool {
+        let (index, candidates) = match self.branch_cell() {
+            Some(pair) => pair,
+            None => return self.is_solved(),
+        };
+
+        if candidates.is_empty() {
+            return false;
+        }
+
+        for value in candidates {
+            let mut candidate = self.clone();
+            candidate.update(value, index);
+
+            if candidate.deduce().is_err() {
+                continue;
+            }
+
+            if candidate.is_solved() || candidate.search() {
+                *self = candidate;
+                return true;
+            }
+        }
+
+        false
+    }
+
+    pub fn solve(&mut self) -> Result<(), SudokuError> {
+        self.deduce()?;
+
+        if self.is_solved() {
+            return Ok(());
+        }
+
+        if self.search() {
+            Ok(())
+        } else {
+            Err(SudokuError::Unsolvable)
+        }
+    }
+
+    // Counts distinct solutions up to `limit`, without mutating `self`.
+    // Stops exploring as soon as `limit` is reached, so `count_solutions(2)`
+    // cheaply tells apart "no solution", "unique", and "multiple".
+    pub fn count_solutions(&self, limit: usize) -> usize {
+        let mut puzzle = self.clone();
+
+        if puzzle.deduce().is_err() {
+            return 0;
+        }
+
+        let mut count = 0;
+        puzzle.tally_solutions(limit, &mut count);
+
+        count
+    }
+
+    fn tally_solutions(&self, limit: usize, count: &mut usize) {
+        if *count >= limit {
+            return;
+        }
+
+        if self.is_solved() {
+            *count += 1;
+            return;
+        }
+
+        let (index, candidates) = match self.branch_cell() {
+            Some(pair) => pair,
+            None => return,
+        };
+
+        for value in candidates {
+            if *count >= limit {
+                return;
+            }
+
+            let mut candidate = self.clone();
+            candidate.update(value, index);
+
+            if candidate.deduce().is_err() {
+                continue;
+            }
+
+            candidate.tally_solutions(limit, count);
+        }
+    }
+
+    pub fn has_unique_solution(&self) -> bool {
+        self.count_solutions(2) == 1
+    }
+
+    // Naked single: a cell with only one remaining candidate value.
+    fn naked_single_pass(&mut self) -> Vec<SolveStep> {
+        let mut steps = vec![];
+
+        for index in 0..Board::<C>::A as u32 {
+            if self.is_occupied(index) {
+                continue;
+            }
+
+            let candidates = self.candidates(index);
+            if candidates.len() == 1 {
+                let value = candidates[0];
+                self.update(value, index);
+                steps.push(SolveStep { technique: Technique::NakedSingle, value, index });
+            }
+        }
+
+        steps
+    }
+
+    // Locked candidates (pointing): if every open position for a value
+    // within a box lies on a single row or column, the value must end up
+    // there, so it can be blotted out of the rest of that line.
+    fn locked_candidate_pass(&mut self) -> Vec<SolveStep> {
+        let l = Board::<C>::L;
+        let mut steps = vec![];
+
+        for box_row in 0..C {
+            for box_col in 0..C {
+                let anchor = (C*l*box_row + C*box_col) as u32;
+                let cell_mask = Mask::<C>::cell(anchor);
+
+                for layer_index in 0..l {
+                    let open: Vec<u32> = (0..C)
+                        .flat_map(|r| (0..C).map(move |c| (r, c)))
+                        .map(|(r, c)| anchor + (l*r + c) as u32)
+                        .filter(|&index| !self.layers[layer_index].mask[index as usize])
+                        .collect();
+
+                    if open.is_empty() {
+                        continue;
+                    }
+
+                    let rows: HashSet<u32> = open.iter().map(|&index| index / l as u32).collect();
+                    let columns: HashSet<u32> = open.iter().map(|&index| index % l as u32).collect();
+                    let value = self.layers[layer_index].value;
+
+                    if rows.len() == 1 {
+                        let row = *rows.iter().next().unwrap();
+                        let line = Mask::<C>::row(row * l as u32);
+                        if self.blot_outside(layer_index, &line, &cell_mask) {
+                            steps.push(SolveStep { technique: Technique::LockedCandidate, value, index: anchor });
+                        }
+                    }
+
+                    if columns.len() == 1 {
+                        let column = *columns.iter().next().unwrap();
+                        let line = Mask::<C>::column(column);
+                        if self.blot_outside(layer_index, &line, &cell_mask) {
+                            steps.push(SolveStep { technique: Technique::LockedCandidate, value, index: anchor });
+                        }
+                    }
+                }
+            }
+        }
+
+        steps
+    }
+
+    // Blots `line` minus `exclude` into the given layer's mask; returns
+    // whether this changed anything.
+    fn blot_outside(&mut self, layer_index: usize, line: &Mask<C>, exclude: &Mask<C>) -> bool {
+        let mut changed = false;
+
+        for index in 0..Board::<C>::A {
+            if line[index] && !exclude[index] && !self.layers[layer_index].mask[index] {
+                self.layers[layer_index].blot(index as u32);
+                changed = true;
+            }
+        }
+
+        changed
+    }
+
+    // Runs the human-style logical techniques (hidden single, naked
+    // single, locked candidates) to a fixpoint, recording each placement
+    // or elimination as a `SolveStep`. Unlike `solve`, this never guesses:
+    // callers can inspect the trace, or check `is_solved()` afterwards, to
+    // rate a puzzle by the hardest technique it required. This is
+    // deliberately a separate method from `solve`/`deduce`: those only
+    // ever escalate from hidden singles to brute-force search, since
+    // naked singles and locked candidates exist here purely to be rated,
+    // not to make plain solving faster.
+    pub fn solve_logical(&mut self) -> Vec<SolveStep> {
+        let mut steps = vec![];
+        let segments = Self::build_segments();
+
+        loop {
+            let before = self.occupied_count();
+
+            // Hidden singles to their own fixpoint before each round falls
+            // through to a "harder" technique. A cell opened up by a naked
+            // single later in the same round is recorded as a naked single
+            // even if a hidden single would also have found it.
+            loop {
+                let before_hidden = self.occupied_count();
+
+                for segment in &segments {
+                    let finds = match segment.find(self) {
+                        Ok(finds) => finds,
+                        Err(_) => return steps,
+                    };
+
+                    for (value, index) in finds {
+                        if !self.is_occupied(index) {
+                            self.update(value, index);
+                            steps.push(SolveStep { technique: Technique::HiddenSingle, value, index });
+                        }
+                    }
+                }
+
+                if self.occupied_count() == before_hidden {
+                    break;
+                }
+            }
+
+            // Then naked singles to their own fixpoint, before locked
+            // candidates (which only eliminates candidates and can in turn
+            // unlock further hidden/naked singles next iteration).
+            loop {
+                let before_naked = self.occupied_count();
+                steps.extend(self.naked_single_pass());
+
+                if self.occupied_count() == before_naked {
+                    break;
+                }
+            }
+
+            let locked = self.locked_candidate_pass();
+            let eliminated = !locked.is_empty();
+            steps.extend(locked);
+
+            if self.occupied_count() == before && !eliminated {
+                break;
+            }
+        }
+
+        steps
+    }
+
+    // Fills the independent diagonal boxes with a shuffled 1..=L each (they
+    // share no row or column, so this can never conflict) to give the
+    // backtracking solver a randomized starting point.
+    fn diagonal_seed<R: Rng + ?Sized>(rng: &mut R) -> Self {
+        let l = Board::<C>::L;
+        let a = Board::<C>::A;
+        let mut feed = vec!['0'; a];
+
+        for box_index in 0..C {
+            let anchor = C*l*box_index + C*box_index;
+
+            let mut values: Vec<u32> = (1..=l as u32).collect();
+            values.shuffle(rng);
+
+            for (offset, &value) in values.iter().enumerate() {
+                let r = offset / C;
+                let c = offset % C;
+                let index = anchor + l*r + c;
+
+                feed[index] = std::char::from_digit(value, RADIX).unwrap();
+            }
+        }
+
+        Self::parse(&feed.into_iter().collect::<String>())
+            .expect("a shuffled diagonal seed is always a valid puzzle")
+    }
+
+    // Clears `index` and re-derives the layers from scratch, since the
+    // layer masks are unions that can't be un-occupied in place.
+    fn without(&self, index: u32) -> Self {
+        let mut readout = self.readout();
+        readout.replace_range(index as usize..index as usize + 1, "0");
+
+        Self::parse(&readout).expect("clearing a clue from a valid puzzle stays valid")
+    }
+
+    pub fn generate_with_rng<R: Rng + ?Sized>(clues: usize, rng: &mut R) -> Self {
+        let mut puzzle = Self::diagonal_seed(rng);
+        puzzle.solve().expect("a diagonal-seeded grid is always solvable");
+
+        let mut order: Vec<u32> = (0..Board::<C>::A as u32).collect();
+        order.shuffle(rng);
+
+        for index in order {
+            if puzzle.occupied_count() <= clues {
+                break;
+            }
+
+            let candidate = puzzle.without(index);
+
+            if candidate.has_unique_solution() {
+                puzzle = candidate;
+            }
+        }
+
+        puzzle
+    }
+
+    pub fn generate(clues: usize) -> Self {
+        Self::generate_with_rng(clues, &mut rand::thread_rng())
     }
 
     pub fn readout(&self) -> String {
-        let mut readout = "0".repeat(SUDOKU_A);
+        let mut readout = "0".repeat(Board::<C>::A);
 
         let positions: HashMap<u32, u32> = self.layers
             .iter()
@@ -218,18 +629,19 @@ impl Puzzle {
 
         for (index, value) in positions {
             let index = index as usize;
-            readout.replace_range(index..index+1, &value.to_string());
+            let digit = std::char::from_digit(value, RADIX).unwrap();
+            readout.replace_range(index..index+1, &digit.to_string());
         }
 
         readout
     }
 }
 
-trait Segment {
-    fn count_layer_positions(&self, layer: &Layer) -> usize;
-    fn locate(&self, layer: &Layer) -> Option<u32>;
+trait Segment<const C: usize> {
+    fn count_layer_positions(&self, layer: &Layer<C>) -> usize;
+    fn locate(&self, layer: &Layer<C>) -> Option<u32>;
 
-    fn is_layer_solved(&self, layer: &Layer) -> Result<bool, CorruptLayerError> {
+    fn is_layer_solved(&self, layer: &Layer<C>) -> Result<bool, CorruptLayerError> {
         match self.count_layer_positions(layer) {
             0 => Ok(false),
             1 => Ok(true),
@@ -239,96 +651,124 @@ trait Segment {
         }
     }
 
-    fn count_open(&self, puzzle: &Puzzle) -> usize {
-        SUDOKU_L as usize - puzzle.layers
-            .iter()
-            .filter(|layer| self.is_layer_solved(layer).unwrap())
-            .count()
+    fn count_open(&self, puzzle: &Puzzle<C>) -> Result<usize, CorruptLayerError> {
+        let mut solved = 0;
+        for layer in puzzle.layers.iter() {
+            if self.is_layer_solved(layer)? {
+                solved += 1;
+            }
+        }
+
+        Ok(Board::<C>::L - solved)
     }
 
-    fn iterate(&self, puzzle: &mut Puzzle) {
+    // Hidden singles: for each layer not yet placed within this segment,
+    // the one open position left for it, if any.
+    fn find(&self, puzzle: &Puzzle<C>) -> Result<Vec<(u32, u32)>, CorruptLayerError> {
         let mut finds = vec![];
 
-        puzzle.layers
-            .iter()
-            .filter(|layer| !self.is_layer_solved(layer).expect("Found corrupted layer!"))
-            .for_each(|layer| {
-                match self.locate(layer) {
-                    Some(index) => finds.push((layer.value, index)),
-                    None => (),
-                };
-            });
+        for layer in puzzle.layers.iter() {
+            if !self.is_layer_solved(layer)? {
+                if let Some(index) = self.locate(layer) {
+                    finds.push((layer.value, index));
+                }
+            }
+        }
 
-        finds
-            .iter()
-            .for_each(|&(value, index)| {
-                puzzle.update(value, index);
-            });
+        Ok(finds)
+    }
+
+    fn iterate(&self, puzzle: &mut Puzzle<C>) -> Result<(), CorruptLayerError> {
+        let finds = self.find(puzzle)?;
+
+        // `finds` is collected from every layer's own, independently
+        // computed hidden single, so two different values can still name
+        // the same index here if they haven't observed each other's
+        // placement yet. Applying one at a time and rejecting an index
+        // that's already occupied turns that clash into the contradiction
+        // it actually is, instead of silently double-occupying the cell.
+        for (value, index) in finds {
+            if puzzle.is_occupied(index) {
+                return Err(CorruptLayerError { value });
+            }
+
+            puzzle.update(value, index);
+        }
+
+        Ok(())
     }
 }
 
 #[derive(Debug)]
-struct Row {
+struct Row<const C: usize> {
     index: u32,
 }
 
-impl Row {
+impl<const C: usize> Row<C> {
     fn new(row: u32) -> Self {
-        assert!(row >= 1 && row <= SUDOKU_L);
-        Self { index: SUDOKU_L * (row - 1) }
+        let l = Board::<C>::L as u32;
+        assert!(row >= 1 && row <= l);
+        Self { index: l * (row - 1) }
     }
 }
 
-impl Segment for Row {
-    fn locate(&self, layer: &Layer) -> Option<u32> {
-        let mut row_mask = Mask::row(self.index);
+impl<const C: usize> Segment<C> for Row<C> {
+    fn locate(&self, layer: &Layer<C>) -> Option<u32> {
+        let l = Board::<C>::L;
+
+        let mut row_mask = Mask::<C>::row(self.index);
         *row_mask &= &*layer.mask;
 
         match row_mask.count_ones() {
-            8 => {
+            n if n == l - 1 => {
                 let s = self.index as usize;
-                let index = row_mask[s..s + SUDOKU_L as usize].first_zero().unwrap() + s;
+                let index = row_mask[s..s + l].first_zero().unwrap() + s;
                 Some(index as u32)
             },
             _ => None,
         }
     }
 
-    fn count_layer_positions(&self, layer: &Layer) -> usize {
+    fn count_layer_positions(&self, layer: &Layer<C>) -> usize {
+        let l = Board::<C>::L as u32;
+
         layer.indices
             .iter()
-            .filter(|&&i| i >= SUDOKU_L*self.index && i < SUDOKU_L*(self.index + 1))
+            .filter(|&&i| i >= self.index && i < self.index + l)
             .count()
     }
 }
 
 #[derive(Debug)]
-struct Column {
+struct Column<const C: usize> {
     index: u32,
 }
 
-impl Column {
+impl<const C: usize> Column<C> {
     fn new(column: u32) -> Self {
-        assert!(column >= 1 && column <= SUDOKU_L);
+        let l = Board::<C>::L as u32;
+        assert!(column >= 1 && column <= l);
         Self { index: column - 1 }
     }
 }
 
-impl Segment for Column {
-    fn locate(&self, layer: &Layer) -> Option<u32> {
-        let mut column_mask = Mask::column(self.index);
+impl<const C: usize> Segment<C> for Column<C> {
+    fn locate(&self, layer: &Layer<C>) -> Option<u32> {
+        let l = Board::<C>::L;
+
+        let mut column_mask = Mask::<C>::column(self.index);
         *column_mask &= &*layer.mask;
 
         match column_mask.count_ones() {
-            8 => {
-                let l = column_mask
+            n if n == l - 1 => {
+                let found = column_mask
                     .iter_ones()
                     .enumerate()
-                    .take_while(|(i, index)| self.index as usize + (SUDOKU_L as usize)*i == *index)
+                    .take_while(|(i, index)| self.index as usize + l*i == *index)
                     .last();
 
-                let s = match l {
-                    Some((_, index)) => index as u32 + SUDOKU_L,
+                let s = match found {
+                    Some((_, index)) => index as u32 + l as u32,
                     None => self.index,
                 };
 
@@ -338,60 +778,70 @@ impl Segment for Column {
         }
     }
 
-    fn count_layer_positions(&self, layer: &Layer) -> usize {
+    fn count_layer_positions(&self, layer: &Layer<C>) -> usize {
+        let l = Board::<C>::L as u32;
+
         layer.indices
             .iter()
-            .filter(|&&i| i % SUDOKU_L == self.index)
+            .filter(|&&i| i % l == self.index)
             .count()
     }
 }
 
 #[derive(Debug)]
-struct Cell {
+struct Cell<const C: usize> {
     index: u32,
 }
 
-impl Cell {
+impl<const C: usize> Cell<C> {
     fn new(i: u32, j: u32) -> Self {
-        assert!(i >= 1 && i <= SUDOKU_C);
-        assert!(j >= 1 && j <= SUDOKU_C);
-        Self { index: SUDOKU_C*SUDOKU_L*(i - 1) + SUDOKU_C*(j - 1) }
+        let l = Board::<C>::L as u32;
+        let c = C as u32;
+        assert!(i >= 1 && i <= c);
+        assert!(j >= 1 && j <= c);
+        Self { index: c*l*(i - 1) + c*(j - 1) }
     }
 }
 
-impl Segment for Cell {
-    fn locate(&self, layer: &Layer) -> Option<u32> {
-        let mut cell_mask = Mask::cell(self.index);
+impl<const C: usize> Segment<C> for Cell<C> {
+    fn locate(&self, layer: &Layer<C>) -> Option<u32> {
+        let l = Board::<C>::L;
+        let c = C;
+
+        let mut cell_mask = Mask::<C>::cell(self.index);
         *cell_mask &= &*layer.mask;
 
         match cell_mask.count_ones() {
-            8 => {
-                let l = cell_mask
+            n if n == l - 1 => {
+                let found = cell_mask
                     .iter_ones()
                     .enumerate()
-                    .take_while(|&(i, index)| self.index + SUDOKU_L*(i as u32 / SUDOKU_C) + i as u32 % SUDOKU_C == index as u32)
+                    .take_while(|&(i, index)| self.index as usize + l*(i / c) + i % c == index)
                     .last();
 
-                let s = match l {
+                let s = match found {
                     Some((i, _)) => {
-                        let i = i as u32 + 1;
-                        self.index + SUDOKU_L*(i / SUDOKU_C) + i % SUDOKU_C
+                        let i = i + 1;
+                        self.index as usize + l*(i / c) + i % c
                     },
-                    None => self.index,
+                    None => self.index as usize,
                 };
 
-                Some(s)
+                Some(s as u32)
             },
             _ => None,
         }
     }
 
-    fn count_layer_positions(&self, layer: &Layer) -> usize {
+    fn count_layer_positions(&self, layer: &Layer<C>) -> usize {
+        let l = Board::<C>::L as u32;
+        let c = C as u32;
+
         layer.indices
             .iter()
             .filter(|&&i| {
-                if i >= self.index && i < self.index + (SUDOKU_C - 1)*SUDOKU_L + SUDOKU_C {
-                    (i - self.index) % SUDOKU_L < SUDOKU_C
+                if i >= self.index && i < self.index + (c - 1)*l + c {
+                    (i - self.index) % l < c
                 } else {
                     false
                 }
@@ -406,7 +856,7 @@ mod tests {
 
     #[test]
     fn mask_new() {
-        let mask = Mask::new();
+        let mask = Mask::<3>::new();
         assert_eq!(mask.deref(), bits![
             0, 0, 0, 0, 0, 0, 0, 0, 0,
             0, 0, 0, 0, 0, 0, 0, 0, 0,
@@ -422,7 +872,7 @@ mod tests {
 
     #[test]
     fn mask_cell() {
-        let mask = Mask::cell(3);
+        let mask = Mask::<3>::cell(3);
         assert_eq!(mask.deref(), bits![
             0, 0, 0, 1, 1, 1, 0, 0, 0,
             0, 0, 0, 1, 1, 1, 0, 0, 0,
@@ -438,7 +888,7 @@ mod tests {
 
     #[test]
     fn mask_row() {
-        let mask = Mask::row(18);
+        let mask = Mask::<3>::row(18);
         assert_eq!(mask.deref(), bits![
             0, 0, 0, 0, 0, 0, 0, 0, 0,
             0, 0, 0, 0, 0, 0, 0, 0, 0,
@@ -454,7 +904,7 @@ mod tests {
 
     #[test]
     fn mask_column() {
-        let mask = Mask::column(8);
+        let mask = Mask::<3>::column(8);
         assert_eq!(mask.deref(), bits![
             0, 0, 0, 0, 0, 0, 0, 0, 1,
             0, 0, 0, 0, 0, 0, 0, 0, 1,
@@ -470,7 +920,7 @@ mod tests {
 
     #[test]
     fn layer_blot() {
-        let mut layer = Layer::new(1);
+        let mut layer = Layer::<3>::new(1);
         layer.blot(0);
         layer.blot(34);
         layer.blot(80);
@@ -490,7 +940,7 @@ mod tests {
 
     #[test]
     fn layer_occupy() {
-        let mut layer = Layer::new(1);
+        let mut layer = Layer::<3>::new(1);
         layer.occupy(0);
         layer.occupy(34);
         layer.occupy(80);
@@ -510,7 +960,7 @@ mod tests {
 
     #[test]
     fn puzzle_parse() {
-        let puzzle = Puzzle::parse("029306807000702050607100002005009010000080000004610000040060080061874209708031005");
+        let puzzle = Puzzle::<3>::parse("029306807000702050607100002005009010000080000004610000040060080061874209708031005").unwrap();
 
         assert_eq!(puzzle.layers[0].indices.len(), 5);
         assert_eq!(puzzle.layers[1].indices.len(), 4);
@@ -525,67 +975,67 @@ mod tests {
 
     #[test]
     fn segment_row_count_open() {
-        let puzzle = Puzzle::parse("029306807000702050607100002005009010000080000004610000040060080061874209708031005");
+        let puzzle = Puzzle::<3>::parse("029306807000702050607100002005009010000080000004610000040060080061874209708031005").unwrap();
 
-        let row = Row::new(1);
-        assert_eq!(row.count_open(&puzzle), 3);
+        let row = Row::<3>::new(1);
+        assert_eq!(row.count_open(&puzzle), Ok(3));
     }
 
     #[test]
     fn segment_column_count_open() {
-        let puzzle = Puzzle::parse("029306807000702050607100002005009010000080000004610000040060080061874209708031005");
+        let puzzle = Puzzle::<3>::parse("029306807000702050607100002005009010000080000004610000040060080061874209708031005").unwrap();
 
-        let column = Column::new(1);
-        assert_eq!(column.count_open(&puzzle), 7);
+        let column = Column::<3>::new(1);
+        assert_eq!(column.count_open(&puzzle), Ok(7));
     }
 
     #[test]
     fn segment_cell_count_open() {
-        let puzzle = Puzzle::parse("029306807000702050607100002005009010000080000004610000040060080061874209708031005");
+        let puzzle = Puzzle::<3>::parse("029306807000702050607100002005009010000080000004610000040060080061874209708031005").unwrap();
 
-        let cell = Cell::new(1, 1);
-        assert_eq!(cell.count_open(&puzzle), 5);
+        let cell = Cell::<3>::new(1, 1);
+        assert_eq!(cell.count_open(&puzzle), Ok(5));
     }
 
     #[test]
     fn segment_cell_count_layer_positions() {
-        let puzzle = Puzzle::parse("029306807000702056607100002005009610000080000004610000040060080061874209708031005");
+        let puzzle = Puzzle::<3>::parse("029306807000702056607100002005009610000080000004610000040060080061874209708031005").unwrap();
 
-        let cell = Cell::new(1, 3);
+        let cell = Cell::<3>::new(1, 3);
         assert_eq!(cell.count_layer_positions(&puzzle.layers[5]), 1);
     }
 
     #[test]
     fn segment_row_locate_1() {
         // In the first row we can place a 1 at the first position
-        let puzzle = Puzzle::parse("029306807000702050607100002005009010000080000004610000040060080061874209708031005");
+        let puzzle = Puzzle::<3>::parse("029306807000702050607100002005009010000080000004610000040060080061874209708031005").unwrap();
 
-        let row = Row::new(1);
+        let row = Row::<3>::new(1);
         assert_eq!(row.locate(&puzzle.layers[0]), Some(0));
     }
 
     #[test]
     fn segment_column_locate_2() {
         // In the fifth column we can place a 2 at the fourth position
-        let puzzle = Puzzle::parse("029306807000702050607100002005009010000080000004610000040060080061874209708031005");
+        let puzzle = Puzzle::<3>::parse("029306807000702050607100002005009010000080000004610000040060080061874209708031005").unwrap();
 
-        let column = Column::new(5);
+        let column = Column::<3>::new(5);
         assert_eq!(column.locate(&puzzle.layers[1]), Some(31));
     }
 
     #[test]
     fn segment_cell_locate_7() {
         // In the bottom right cell we can place a 7 at the first position
-        let puzzle = Puzzle::parse("029306807000702050607100002005009010000080000004610000040060080061874209708031005");
+        let puzzle = Puzzle::<3>::parse("029306807000702050607100002005009010000080000004610000040060080061874209708031005").unwrap();
 
-        let cell = Cell::new(3, 3);
+        let cell = Cell::<3>::new(3, 3);
         assert_eq!(cell.locate(&puzzle.layers[6]), Some(60));
     }
 
     #[test]
     fn puzzle_readout() {
         // In the bottom right cell we can place a 7 at the first position
-        let puzzle = Puzzle::parse("029306807000702050607100002005009010000080000004610000040060080061874209708031005");
+        let puzzle = Puzzle::<3>::parse("029306807000702050607100002005009010000080000004610000040060080061874209708031005").unwrap();
 
         assert_eq!(puzzle.readout(), String::from("029306807000702050607100002005009010000080000004610000040060080061874209708031005"));
     }
@@ -593,19 +1043,200 @@ mod tests {
     #[test]
     fn puzzle_row_iteration() {
         // In the bottom right cell we can place a 7 at the first position
-        let mut puzzle = Puzzle::parse("029306807000702050607100002005009010000080000004610000040060080061874209708031005");
+        let mut puzzle = Puzzle::<3>::parse("029306807000702050607100002005009010000080000004610000040060080061874209708031005").unwrap();
 
-        let row = Row::new(1);
-        row.iterate(&mut puzzle);
+        let row = Row::<3>::new(1);
+        row.iterate(&mut puzzle).unwrap();
         assert_eq!(puzzle.readout(), String::from("129306807000702050607100002005009010000080000004610000040060080061874209708031005"));
     }
 
     #[test]
     fn puzzle_solve() {
         // In the bottom right cell we can place a 7 at the first position
-        let mut puzzle = Puzzle::parse("029306807000702050607100002005009010000080000004610000040060080061874209708031005");
+        let mut puzzle = Puzzle::<3>::parse("029306807000702050607100002005009010000080000004610000040060080061874209708031005").unwrap();
 
-        puzzle.solve();
+        assert!(puzzle.solve().is_ok());
         assert_eq!(puzzle.readout(), String::from("129356847483792156657148392875429613216583974934617528342965781561874239798231465"));
     }
+
+    #[test]
+    fn puzzle_solve_detects_a_globally_unsolvable_grid() {
+        // No segment has a duplicate given, so this parses fine: the first
+        // row holds 1..8 with the last cell open, and a single 9 sits
+        // elsewhere in the top-right box. But that box constraint then
+        // blocks the open cell from ever taking the 9 the row needs, so no
+        // completion exists.
+        let feed = "123456780000000090".to_string() + &"0".repeat(63);
+        let mut puzzle = Puzzle::<3>::parse(&feed).unwrap();
+
+        assert_eq!(puzzle.solve(), Err(SudokuError::Unsolvable));
+    }
+
+    #[test]
+    fn puzzle_solve_detects_two_values_deducing_the_same_cell() {
+        // Each of these givens parses fine on its own, but they set up a
+        // 4x4 board where hidden singles for value 1 and value 2 are each
+        // independently computed (within one deduction pass) to be the
+        // only open position left in their own row/column/box — and they
+        // both land on cell 0. Applying one must now invalidate the other
+        // instead of letting both silently occupy it.
+        let feed = "0000002101020200";
+        let mut puzzle = Puzzle::<2>::parse(feed).unwrap();
+
+        assert_eq!(puzzle.solve(), Err(SudokuError::CorruptLayer { value: 2 }));
+    }
+
+    #[test]
+    fn puzzle_parse_rejects_contradictory_givens() {
+        // Two givens claiming the same value in the same row can never be
+        // reconciled, so parse() must reject the feed instead of building
+        // a puzzle that would later loop or panic.
+        let feed = "110000000".to_string() + &"0".repeat(72);
+
+        assert!(matches!(Puzzle::<3>::parse(&feed), Err(SudokuError::CorruptLayer { value: 1 })));
+    }
+
+    #[test]
+    fn puzzle_parse_rejects_wrong_length() {
+        assert!(matches!(
+            Puzzle::<3>::parse("029"),
+            Err(SudokuError::InvalidLength { expected: 81, found: 3 }),
+        ));
+    }
+
+    #[test]
+    fn puzzle_parse_rejects_invalid_character() {
+        let feed = "x".to_string() + &"0".repeat(80);
+
+        assert!(matches!(Puzzle::<3>::parse(&feed), Err(SudokuError::InvalidDigit { character: 'x' })));
+    }
+
+    #[test]
+    fn puzzle_has_unique_solution() {
+        let puzzle = Puzzle::<3>::parse("029306807000702050607100002005009010000080000004610000040060080061874209708031005").unwrap();
+
+        assert!(puzzle.has_unique_solution());
+    }
+
+    #[test]
+    fn puzzle_count_solutions_caps_at_limit() {
+        // An empty grid admits far more than two solutions, so the tally
+        // should stop exploring once the limit is reached.
+        let puzzle = Puzzle::<3>::parse(&"0".repeat(81)).unwrap();
+
+        assert_eq!(puzzle.count_solutions(2), 2);
+    }
+
+    #[test]
+    fn puzzle_count_solutions_leaves_original_untouched() {
+        let puzzle = Puzzle::<3>::parse("029306807000702050607100002005009010000080000004610000040060080061874209708031005").unwrap();
+
+        puzzle.count_solutions(2);
+
+        assert_eq!(puzzle.readout(), String::from("029306807000702050607100002005009010000080000004610000040060080061874209708031005"));
+    }
+
+    #[test]
+    fn puzzle_generate_with_rng_is_reproducible() {
+        let mut rng_a = rand::rngs::StdRng::seed_from_u64(42);
+        let puzzle_a = Puzzle::<3>::generate_with_rng(30, &mut rng_a);
+
+        let mut rng_b = rand::rngs::StdRng::seed_from_u64(42);
+        let puzzle_b = Puzzle::<3>::generate_with_rng(30, &mut rng_b);
+
+        assert_eq!(puzzle_a.readout(), puzzle_b.readout());
+    }
+
+    #[test]
+    fn puzzle_generate_has_unique_solution() {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(7);
+        let puzzle = Puzzle::<3>::generate_with_rng(30, &mut rng);
+
+        assert!(puzzle.has_unique_solution());
+        assert!(puzzle.occupied_count() >= 30);
+    }
+
+    #[test]
+    fn puzzle_generate_with_rng_never_leaves_blank_cells() {
+        // These seeds used to hit the same cross-layer deduction clash
+        // `puzzle_solve_detects_two_values_deducing_the_same_cell` covers:
+        // `has_unique_solution` silently reported a corrupted candidate as
+        // uniquely solved, so the generator kept removing clues past the
+        // point where the board was still genuinely solvable.
+        for seed in [13, 15, 16, 17, 19, 22, 24, 25, 28, 29, 30, 32, 33, 34, 35, 39] {
+            let mut rng = rand::rngs::StdRng::seed_from_u64(seed);
+            let puzzle = Puzzle::<2>::generate_with_rng(6, &mut rng);
+
+            assert!(puzzle.has_unique_solution(), "seed {seed}");
+
+            let mut solved = puzzle.clone();
+            assert!(solved.solve().is_ok(), "seed {seed}");
+            assert!(!solved.readout().contains('0'), "seed {seed}");
+        }
+    }
+
+    #[test]
+    fn puzzle_solves_4x4_board() {
+        // A 2x2-box (4x4) board, missing the single clue at index 0.
+        let mut puzzle = Puzzle::<2>::parse("0234341221434321").unwrap();
+
+        assert!(puzzle.solve().is_ok());
+        assert_eq!(puzzle.readout(), String::from("1234341221434321"));
+    }
+
+    #[test]
+    fn puzzle_parses_16x16_board_with_letter_digits() {
+        // A complete 4x4-box (16x16) grid, values above 9 spelled out as
+        // 'a'..='g', round-tripping through the RADIX = 36 encoding.
+        let feed = "123456789abcdefg56789abcdefg12349abcdefg12345678defg123456789abc\
+23456789abcdefg16789abcdefg12345abcdefg123456789efg123456789abcd\
+3456789abcdefg12789abcdefg123456bcdefg123456789afg123456789abcde\
+456789abcdefg12389abcdefg1234567cdefg123456789abg123456789abcdef";
+
+        let puzzle = Puzzle::<4>::parse(feed).unwrap();
+
+        assert!(puzzle.is_solved());
+        assert_eq!(puzzle.readout(), String::from(feed));
+    }
+
+    #[test]
+    fn puzzle_solves_16x16_board() {
+        // Same complete grid as above, missing its first clue.
+        let feed = "023456789abcdefg56789abcdefg12349abcdefg12345678defg123456789abc\
+23456789abcdefg16789abcdefg12345abcdefg123456789efg123456789abcd\
+3456789abcdefg12789abcdefg123456bcdefg123456789afg123456789abcde\
+456789abcdefg12389abcdefg1234567cdefg123456789abg123456789abcdef";
+
+        let mut puzzle = Puzzle::<4>::parse(feed).unwrap();
+
+        assert!(puzzle.solve().is_ok());
+        assert_eq!(
+            puzzle.readout(),
+            String::from("123456789abcdefg56789abcdefg12349abcdefg12345678defg123456789abc\
+23456789abcdefg16789abcdefg12345abcdefg123456789efg123456789abcd\
+3456789abcdefg12789abcdefg123456bcdefg123456789afg123456789abcde\
+456789abcdefg12389abcdefg1234567cdefg123456789abg123456789abcdef"),
+        );
+    }
+
+    #[test]
+    fn puzzle_solve_logical_solves_with_hidden_singles_only() {
+        let mut puzzle = Puzzle::<3>::parse("029306807000702050607100002005009010000080000004610000040060080061874209708031005").unwrap();
+
+        let steps = puzzle.solve_logical();
+
+        assert!(puzzle.is_solved());
+        assert!(!steps.is_empty());
+        assert!(steps.iter().all(|step| step.technique == Technique::HiddenSingle));
+    }
+
+    #[test]
+    fn puzzle_solve_logical_makes_no_progress_on_an_empty_board() {
+        let mut puzzle = Puzzle::<3>::parse(&"0".repeat(81)).unwrap();
+
+        let steps = puzzle.solve_logical();
+
+        assert!(steps.is_empty());
+        assert!(!puzzle.is_solved());
+    }
 }